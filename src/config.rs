@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::errors::*;
+use crate::log::Level;
+
+/// Which segments `DisplayFields::to_string()` renders, and in what order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Volume,
+    Net,
+    Systemstat,
+    Temp,
+    Time,
+}
+
+impl Field {
+    fn from_str(s: &str) -> Option<Field> {
+        match s {
+            "volume" => Some(Field::Volume),
+            "net" => Some(Field::Net),
+            "systemstat" => Some(Field::Systemstat),
+            "temp" => Some(Field::Temp),
+            "time" => Some(Field::Time),
+            _ => None,
+        }
+    }
+}
+
+const DEFAULT_ORDER: [Field; 5] = [
+    Field::Volume,
+    Field::Net,
+    Field::Systemstat,
+    Field::Temp,
+    Field::Time,
+];
+
+/// Emoji/glyphs used across the segments. Overridable via `icon.<name>=` lines
+/// so users can swap in ASCII or localized glyphs without recompiling.
+#[derive(Debug, Clone)]
+pub struct Icons {
+    pub battery_ac: String,
+    pub battery_batt: String,
+    pub volume_mute: String,
+    pub volume_low: String,
+    pub volume_med: String,
+    pub volume_high: String,
+    pub net_wireless: String,
+    pub net_wired: String,
+    pub temp: String,
+    pub ram: String,
+    pub cpu: String,
+    pub calendar: String,
+    pub clock: String,
+}
+
+impl Default for Icons {
+    fn default() -> Icons {
+        Icons {
+            battery_ac: "🔌".to_string(),
+            battery_batt: "🔋".to_string(),
+            volume_mute: "🔇".to_string(),
+            volume_low: "🔈".to_string(),
+            volume_med: "🔉".to_string(),
+            volume_high: "🔊".to_string(),
+            net_wireless: "📡".to_string(),
+            net_wired: "⇅".to_string(),
+            temp: "\u{1F321}".to_string(),
+            ram: "▯".to_string(),
+            cpu: "⚙".to_string(),
+            calendar: "📆".to_string(),
+            clock: "🕓".to_string(),
+        }
+    }
+}
+
+/// Settings for the optional MQTT publisher thread. Disabled unless
+/// `mqtt.host` is set in the config file.
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for MqttConfig {
+    fn default() -> MqttConfig {
+        MqttConfig {
+            host: None,
+            port: 1883,
+            username: None,
+            password: None,
+        }
+    }
+}
+
+impl MqttConfig {
+    pub fn enabled(&self) -> bool {
+        self.host.is_some()
+    }
+}
+
+/// Settings for the opt-in file logger. Disabled unless `log.path` is set.
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub path: Option<PathBuf>,
+    pub level: Level,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            path: None,
+            level: Level::Warn,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub order: Vec<Field>,
+    pub poll_secs: u64,
+    pub time_format: String,
+    pub icons: Icons,
+    pub sensors_chip: Option<String>,
+    pub mqtt: MqttConfig,
+    pub log: LogConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            order: DEFAULT_ORDER.to_vec(),
+            poll_secs: 5,
+            time_format: "%a, %d %h".to_string(),
+            icons: Icons::default(),
+            sensors_chip: None,
+            mqtt: MqttConfig::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let mut path = dirs::config_dir()?;
+    path.push("dwmstatus");
+    path.push("config.txt");
+    Some(path)
+}
+
+/// Parses `key=value` lines, one per line, `#` comments and blank lines ignored.
+fn parse(contents: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+fn apply(values: &HashMap<String, String>, config: &mut Config) {
+    if let Some(order) = values.get("order") {
+        let fields: Vec<Field> = order.split(',').filter_map(|s| Field::from_str(s.trim())).collect();
+        if !fields.is_empty() {
+            config.order = fields;
+        }
+    }
+    if let Some(poll_secs) = values.get("poll_secs").and_then(|v| v.parse().ok()) {
+        config.poll_secs = poll_secs;
+    }
+    if let Some(format) = values.get("time.format") {
+        config.time_format = format.clone();
+    }
+    if let Some(chip) = values.get("sensors.chip") {
+        config.sensors_chip = Some(chip.clone());
+    }
+    if let Some(host) = values.get("mqtt.host") {
+        config.mqtt.host = Some(host.clone());
+    }
+    if let Some(port) = values.get("mqtt.port").and_then(|v| v.parse().ok()) {
+        config.mqtt.port = port;
+    }
+    if let Some(username) = values.get("mqtt.username") {
+        config.mqtt.username = Some(username.clone());
+    }
+    if let Some(password) = values.get("mqtt.password") {
+        config.mqtt.password = Some(password.clone());
+    }
+    if let Some(path) = values.get("log.path") {
+        config.log.path = Some(PathBuf::from(path));
+    }
+    if let Some(level) = values.get("log.level").and_then(|v| Level::from_str(v)) {
+        config.log.level = level;
+    }
+
+    macro_rules! icon {
+        ($key:expr, $field:ident) => {
+            if let Some(value) = values.get(concat!("icon.", $key)) {
+                config.icons.$field = value.clone();
+            }
+        };
+    }
+    icon!("battery_ac", battery_ac);
+    icon!("battery_batt", battery_batt);
+    icon!("volume_mute", volume_mute);
+    icon!("volume_low", volume_low);
+    icon!("volume_med", volume_med);
+    icon!("volume_high", volume_high);
+    icon!("net_wireless", net_wireless);
+    icon!("net_wired", net_wired);
+    icon!("temp", temp);
+    icon!("ram", ram);
+    icon!("cpu", cpu);
+    icon!("time_cal", calendar);
+    icon!("time_clock", clock);
+}
+
+/// Loads `~/.config/dwmstatus/config.txt` if present, falling back to defaults
+/// when the file is missing. A malformed path (e.g. no `$HOME`) also falls
+/// back to defaults rather than failing startup.
+pub fn load() -> Result<Config> {
+    let mut config = Config::default();
+    let path = match default_path() {
+        Some(path) => path,
+        None => return Ok(config),
+    };
+    match fs::read_to_string(&path) {
+        Ok(contents) => {
+            apply(&parse(&contents), &mut config);
+            Ok(config)
+        }
+        Err(ref err) if err.kind() == std::io::ErrorKind::NotFound => Ok(config),
+        Err(err) => Err(err).chain_err(|| format!("reading {}", path.display())),
+    }
+}
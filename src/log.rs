@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::Local;
+use lazy_static::lazy_static;
+
+/// Log levels, most to least severe. A configured level suppresses anything
+/// less severe than itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+}
+
+impl Level {
+    pub fn from_str(s: &str) -> Option<Level> {
+        match s {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+        }
+    }
+}
+
+struct State {
+    file: Option<std::fs::File>,
+    level: Level,
+    /// Whether each named check was failing the last time it reported in,
+    /// so `report_failure` can log only on the failing/recovered transition
+    /// instead of once per poll of an ordinary, permanent condition.
+    failing: HashMap<&'static str, bool>,
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        file: None,
+        level: Level::Warn,
+        failing: HashMap::new(),
+    });
+}
+
+/// Opens the configured log file, if any. Called once from `main`; every
+/// thread can log afterward through the `log_warn!`/`log_error!` macros.
+/// Logging is a silent no-op until this runs, and stays a no-op if no log
+/// path is configured.
+pub fn init(path: Option<&Path>, level: Level) {
+    let file = path.and_then(|path| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .ok()
+    });
+    let mut state = STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    state.file = file;
+    state.level = level;
+}
+
+fn write_line(state: &mut State, level: Level, args: std::fmt::Arguments) {
+    if level > state.level {
+        return;
+    }
+    if let Some(file) = state.file.as_mut() {
+        let _ = writeln!(
+            file,
+            "{} [{}] {}",
+            Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            level.label(),
+            args
+        );
+    }
+}
+
+#[doc(hidden)]
+pub fn emit(level: Level, args: std::fmt::Arguments) {
+    let mut state = STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    write_line(&mut state, level, args);
+}
+
+/// Logs a warning on the first failure under `key` and an info line on the
+/// first recovery, staying silent on every repeat in between. `detail` is
+/// `Some(reason)` for a failing tick, `None` once the check succeeds again.
+/// Without this de-duplication an ordinary, permanent condition (no battery,
+/// broker down, no route) would write an identical line on every poll
+/// forever.
+#[doc(hidden)]
+pub fn report_failure(key: &'static str, detail: Option<std::fmt::Arguments>) {
+    let mut state = STATE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let failed = detail.is_some();
+    let was_failed = state.failing.get(key).copied().unwrap_or(false);
+    if failed == was_failed {
+        return;
+    }
+    state.failing.insert(key, failed);
+    match detail {
+        Some(args) => write_line(&mut state, Level::Warn, args),
+        None => write_line(&mut state, Level::Info, format_args!("{} recovered", key)),
+    }
+}
+
+/// Logs a warning line if logging is enabled and the configured level allows it.
+macro_rules! log_warn {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Warn, format_args!($($arg)*))
+    };
+}
+
+/// Logs an error line if logging is enabled and the configured level allows it.
+macro_rules! log_error {
+    ($($arg:tt)*) => {
+        $crate::log::emit($crate::log::Level::Error, format_args!($($arg)*))
+    };
+}
+
+/// Logs a warning the first time `$key` starts failing, and an info line the
+/// first time it recovers; repeats of the same state are suppressed. Use for
+/// checks that re-run every tick (subprocess calls, sensor/proc reads) so a
+/// permanent condition doesn't spam the log forever.
+macro_rules! log_failure {
+    ($key:expr, $($arg:tt)*) => {
+        $crate::log::report_failure($key, Some(format_args!($($arg)*)))
+    };
+}
+
+/// Companion to `log_failure!` — call on the success path of the same check
+/// so a prior failure is reported as recovered.
+macro_rules! log_recovery {
+    ($key:expr) => {
+        $crate::log::report_failure($key, None)
+    };
+}
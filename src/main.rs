@@ -11,13 +11,14 @@
 extern crate error_chain;
 
 use std::collections::HashSet;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
-use std::time;
+use std::time::{self, Instant};
 
 use chrono;
 use lazy_static::lazy_static;
@@ -26,49 +27,108 @@ use sensors::{FeatureType, Sensors, Subfeature, SubfeatureType};
 use systemstat::{Platform, System};
 use xcb;
 
+mod config;
 mod errors {
     error_chain! {}
 }
+#[macro_use]
+mod log;
+mod mqtt;
 
+use crate::config::{Config, Field};
 use crate::errors::*;
 
-const POLL_TIME: time::Duration = time::Duration::from_secs(5);
-
 #[derive(Debug)]
-struct DisplayFields {
+pub(crate) struct DisplayFields {
     time: String,
     systemstat: String,
     temp: String,
     net: String,
     volume: String,
+    order: Vec<Field>,
+}
+
+impl DisplayFields {
+    pub(crate) fn time(&self) -> &str {
+        &self.time
+    }
+    pub(crate) fn systemstat(&self) -> &str {
+        &self.systemstat
+    }
+    pub(crate) fn temp(&self) -> &str {
+        &self.temp
+    }
+    pub(crate) fn net(&self) -> &str {
+        &self.net
+    }
+    pub(crate) fn volume(&self) -> &str {
+        &self.volume
+    }
 }
 
 impl ToString for DisplayFields {
     fn to_string(&self) -> String {
-        format!(
-            "{}{}{}{}{}",
-            self.volume, self.net, self.systemstat, self.temp, self.time
-        )
-        .to_string()
+        self.order
+            .iter()
+            .map(|field| match field {
+                Field::Volume => self.volume.as_str(),
+                Field::Net => self.net.as_str(),
+                Field::Systemstat => self.systemstat.as_str(),
+                Field::Temp => self.temp.as_str(),
+                Field::Time => self.time.as_str(),
+            })
+            .collect::<String>()
     }
 }
 
 #[derive(Debug)]
-struct Concurrency {
+pub(crate) struct Concurrency {
     lock: Mutex<DisplayFields>,
     condition: Condvar,
 }
 
-fn time_thread(conc: Arc<Concurrency>) {
+/// A point-in-time copy of every segment plus the combined status string,
+/// handed to consumers (the X property and the MQTT publisher) without
+/// exposing the `DisplayFields` lock to them.
+pub(crate) struct Snapshot {
+    pub(crate) volume: String,
+    pub(crate) net: String,
+    pub(crate) systemstat: String,
+    pub(crate) temp: String,
+    pub(crate) time: String,
+    pub(crate) status: String,
+}
+
+impl Concurrency {
+    /// Blocks until a segment changes, then returns a snapshot of all of them.
+    pub(crate) fn wait_for_change(&self) -> Snapshot {
+        let df = self.lock.lock().unwrap();
+        let df = self.condition.wait(df).unwrap();
+        Snapshot {
+            volume: df.volume().to_string(),
+            net: df.net().to_string(),
+            systemstat: df.systemstat().to_string(),
+            temp: df.temp().to_string(),
+            time: df.time().to_string(),
+            status: df.to_string(),
+        }
+    }
+}
+
+fn time_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
     loop {
-        let new_date = chrono::Local::now().format("📆 %a, %d %h");
+        let new_date = chrono::Local::now().format(&config.time_format);
         let new_utc_time = chrono::Utc::now().format("%HZ");
         let new_local_time = chrono::Local::now().format("%R");
-        let new_time = format!("{} 🕓 {} {}", new_date, new_utc_time, new_local_time).to_string();
+        let new_time = format!(
+            "{} {} {} {} {}",
+            config.icons.calendar, new_date, config.icons.clock, new_utc_time, new_local_time
+        )
+        .to_string();
         {
             let mut df = conc.lock.lock().unwrap();
             df.time = new_time;
-            conc.condition.notify_one();
+            conc.condition.notify_all();
         }
         let now = chrono::Local::now();
         let now_ts = now.timestamp_millis();
@@ -78,60 +138,80 @@ fn time_thread(conc: Arc<Concurrency>) {
     }
 }
 
-fn plugged(sys: &System) -> String {
+fn plugged(sys: &System, config: &Config) -> String {
     if let Ok(plugged) = sys.on_ac_power() {
         if plugged {
-            "🔌".to_string()
+            config.icons.battery_ac.clone()
         } else {
-            "🔋".to_string()
+            config.icons.battery_batt.clone()
         }
     } else {
-        "🔌".to_string()
+        config.icons.battery_ac.clone()
     }
 }
 
-fn battery(sys: &System) -> String {
-    if let Ok(bat) = sys.battery_life() {
-        format!("{} {:.1}% ⸱ ", plugged(sys), bat.remaining_capacity * 100.)
-    } else {
-        "".to_string()
+fn battery(sys: &System, config: &Config) -> String {
+    match sys.battery_life() {
+        Ok(bat) => {
+            log_recovery!("battery_life");
+            format!(
+                "{} {:.1}% ⸱ ",
+                plugged(sys, config),
+                bat.remaining_capacity * 100.
+            )
+        }
+        Err(err) => {
+            log_failure!("battery_life", "battery_life: {}", err);
+            "".to_string()
+        }
     }
 }
 
-fn ram(sys: &System) -> String {
+fn ram(sys: &System, config: &Config) -> String {
     if let Ok(mem) = sys.memory() {
         let used = mem.total - mem.free;
-        format!("▯ {}", used)
+        format!("{} {}", config.icons.ram, used)
     } else {
-        "▯ _".to_string()
+        format!("{} _", config.icons.ram)
     }
 }
 
-fn cpu(sys: &System) -> String {
+fn cpu(sys: &System, config: &Config) -> String {
     if let Ok(load) = sys.load_average() {
-        format!("⚙ {:.2}", load.one)
+        format!("{} {:.2}", config.icons.cpu, load.one)
     } else {
-        "⚙ _".to_string()
+        format!("{} _", config.icons.cpu)
     }
 }
 
-fn systemstat_thread(conc: Arc<Concurrency>) {
+fn systemstat_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
     let sys = System::new();
     loop {
-        let new_stat = format!("{}{} ⸱ {} ⸱ ", battery(&sys), ram(&sys), cpu(&sys)).to_string();
+        let new_stat = format!(
+            "{}{} ⸱ {} ⸱ ",
+            battery(&sys, &config),
+            ram(&sys, &config),
+            cpu(&sys, &config)
+        )
+        .to_string();
         {
             let mut df = conc.lock.lock().unwrap();
             if df.systemstat != new_stat {
                 df.systemstat = new_stat;
-                conc.condition.notify_one();
+                conc.condition.notify_all();
             }
         }
-        thread::sleep(POLL_TIME);
+        thread::sleep(time::Duration::from_secs(config.poll_secs));
     }
 }
 
-fn find_cpu_temp(sensors: &Sensors) -> Option<Subfeature> {
+fn find_cpu_temp(sensors: &Sensors, chip_name: Option<&str>) -> Option<Subfeature> {
     for chip in sensors.into_iter() {
+        if let Some(wanted) = chip_name {
+            if chip.name().ok().map(|name| name != wanted).unwrap_or(true) {
+                continue;
+            }
+        }
         for feature in chip.into_iter() {
             if feature.feature_type() == &FeatureType::SENSORS_FEATURE_TEMP {
                 if let Some(subfeature) =
@@ -145,19 +225,24 @@ fn find_cpu_temp(sensors: &Sensors) -> Option<Subfeature> {
     return None;
 }
 
-fn sensors_thread(conc: Arc<Concurrency>) {
+fn sensors_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
     let sensors = Sensors::new();
-    if let Some(temp_sensor) = find_cpu_temp(&sensors) {
+    let chip_name = config.sensors_chip.as_deref();
+    if let Some(temp_sensor) = find_cpu_temp(&sensors, chip_name) {
         loop {
-            let new_temp = format!("\u{1F321} {:.1} ⸱ ", temp_sensor.get_value().unwrap());
+            let new_temp = format!(
+                "{} {:.1} ⸱ ",
+                config.icons.temp,
+                temp_sensor.get_value().unwrap()
+            );
             {
                 let mut df = conc.lock.lock().unwrap();
                 if df.temp != new_temp {
                     df.temp = new_temp;
-                    conc.condition.notify_one();
+                    conc.condition.notify_all();
                 }
             }
-            thread::sleep(POLL_TIME);
+            thread::sleep(time::Duration::from_secs(config.poll_secs));
         }
     }
 }
@@ -192,10 +277,86 @@ fn get_current_interface() -> Result<String> {
     bail!("No current interface.")
 }
 
-fn network_thread(conc: Arc<Concurrency>) {
-    let wireless = "📡 ⸱ ";
-    let wired = "⇅ ⸱ ";
+fn read_interface_bytes(interface: &str) -> Result<(u64, u64)> {
+    let contents = fs::read_to_string("/proc/net/dev").chain_err(|| "reading /proc/net/dev")?;
+    for line in contents.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.first().map(|f| f.trim_end_matches(':')) != Some(interface) {
+            continue;
+        }
+        let rx = fields
+            .get(1)
+            .and_then(|s| s.parse().ok())
+            .chain_err(|| "parsing rx_bytes")?;
+        let tx = fields
+            .get(9)
+            .and_then(|s| s.parse().ok())
+            .chain_err(|| "parsing tx_bytes")?;
+        return Ok((rx, tx));
+    }
+    bail!("interface {} not found in /proc/net/dev", interface)
+}
+
+fn format_rate(bytes_per_sec: f64) -> String {
+    if bytes_per_sec >= 1024.0 * 1024.0 {
+        format!("{:.1}M", bytes_per_sec / (1024.0 * 1024.0))
+    } else if bytes_per_sec >= 1024.0 {
+        format!("{:.0}K", bytes_per_sec / 1024.0)
+    } else {
+        format!("{:.0}B", bytes_per_sec)
+    }
+}
+
+/// Tracks the previous byte-counter sample so `network_thread` can turn two
+/// `/proc/net/dev` snapshots into a rate. Reset whenever the interface
+/// changes or a counter goes backwards (interface reset).
+struct RateSample {
+    interface: String,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    at: Instant,
+}
+
+fn sample_rate(
+    interface: &str,
+    previous: &Option<RateSample>,
+) -> (Option<(f64, f64)>, Option<RateSample>) {
+    let now = Instant::now();
+    let (rx_bytes, tx_bytes) = match read_interface_bytes(interface) {
+        Ok(bytes) => {
+            log_recovery!("read_interface_bytes");
+            bytes
+        }
+        Err(err) => {
+            log_failure!("read_interface_bytes", "read_interface_bytes({}): {}", interface, err);
+            return (None, None);
+        }
+    };
+    let rates = previous.as_ref().and_then(|prev| {
+        if prev.interface != interface || rx_bytes < prev.rx_bytes || tx_bytes < prev.tx_bytes {
+            return None;
+        }
+        let elapsed = now.duration_since(prev.at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        Some((
+            (rx_bytes - prev.rx_bytes) as f64 / elapsed,
+            (tx_bytes - prev.tx_bytes) as f64 / elapsed,
+        ))
+    });
+    (
+        rates,
+        Some(RateSample {
+            interface: interface.to_string(),
+            rx_bytes,
+            tx_bytes,
+            at: now,
+        }),
+    )
+}
 
+fn network_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
     let wifs = get_wireless_interfaces();
 
     let monitor = Command::new("ip")
@@ -204,28 +365,71 @@ fn network_thread(conc: Arc<Concurrency>) {
         .arg("address")
         .stdout(Stdio::piped())
         .spawn()
-        .unwrap();
+        .unwrap_or_else(|err| {
+            log_error!("spawn ip monitor: {}", err);
+            panic!("spawn ip monitor: {}", err);
+        });
     let mut stdout = monitor.stdout.unwrap();
-    let mut buffer = [0; 1024];
+
+    // `ip monitor` only wakes us on link changes; forward its wakeups onto a
+    // channel so the main loop can also time out on `config.poll_secs` and
+    // keep refreshing the throughput rate in between.
+    let (tx, rx) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let mut buffer = [0; 1024];
+        loop {
+            match stdout.read(&mut buffer) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if tx.send(()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut previous: Option<RateSample> = None;
 
     loop {
-        let new_symbol = match get_current_interface() {
+        let new_net = match get_current_interface() {
             Ok(interface) => {
-                if wifs.contains(&interface) {
-                    wireless
+                log_recovery!("get_current_interface");
+                let symbol = if wifs.contains(&interface) {
+                    &config.icons.net_wireless
                 } else {
-                    wired
+                    &config.icons.net_wired
+                };
+                let (rates, sample) = sample_rate(&interface, &previous);
+                previous = sample;
+                match rates {
+                    Some((rx_rate, tx_rate)) => format!(
+                        "{} ↓{} ↑{} ⸱ ",
+                        symbol,
+                        format_rate(rx_rate),
+                        format_rate(tx_rate)
+                    ),
+                    None => format!("{} ⸱ ", symbol),
                 }
             }
-            Err(_) => "",
-        }
-        .to_string();
+            Err(err) => {
+                log_failure!("get_current_interface", "get_current_interface: {}", err);
+                previous = None;
+                "".to_string()
+            }
+        };
         {
             let mut df = conc.lock.lock().unwrap();
-            df.net = new_symbol;
-            conc.condition.notify_one();
+            if df.net != new_net {
+                df.net = new_net;
+                conc.condition.notify_all();
+            }
+        }
+        match rx.recv_timeout(time::Duration::from_secs(config.poll_secs)) {
+            Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+            // The `ip monitor` reader exited; fall back to polling on a timer.
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(time::Duration::from_secs(config.poll_secs)),
         }
-        stdout.read(&mut buffer).unwrap();
     }
 }
 
@@ -247,25 +451,34 @@ fn get_volume() -> Result<i32> {
     return Ok(volume_string.trim().parse().chain_err(|| "parse")?);
 }
 
-fn volume() -> String {
-    if let Ok(muted) = get_mute() {
-        if muted {
-            return "🔇 ⸱ ".to_string();
+fn volume(config: &Config) -> String {
+    match get_mute() {
+        Ok(true) => {
+            log_recovery!("get_mute");
+            return format!("{} ⸱ ", config.icons.volume_mute);
         }
+        Ok(false) => log_recovery!("get_mute"),
+        Err(err) => log_failure!("get_mute", "get_mute: {}", err),
     }
 
-    if let Ok(volume) = get_volume() {
-        let speaker = match volume {
-            0..=33 => "🔈",
-            34..=66 => "🔉",
-            _ => "🔊",
-        };
-        return format!("{} {} ⸱ ", speaker, volume);
+    match get_volume() {
+        Ok(volume) => {
+            log_recovery!("get_volume");
+            let speaker = match volume {
+                0..=33 => &config.icons.volume_low,
+                34..=66 => &config.icons.volume_med,
+                _ => &config.icons.volume_high,
+            };
+            format!("{} {} ⸱ ", speaker, volume)
+        }
+        Err(err) => {
+            log_failure!("get_volume", "get_volume: {}", err);
+            "".to_string()
+        }
     }
-    return "".to_string();
 }
 
-fn volume_thread(conc: Arc<Concurrency>) {
+fn volume_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
     let re = Regex::new(r"on sink").unwrap();
 
     loop {
@@ -273,17 +486,20 @@ fn volume_thread(conc: Arc<Concurrency>) {
             .arg("subscribe")
             .stdout(Stdio::piped())
             .spawn()
-            .unwrap();
+            .unwrap_or_else(|err| {
+                log_error!("spawn pactl subscribe: {}", err);
+                panic!("spawn pactl subscribe: {}", err);
+            });
         let stdout = monitor.stdout.take().unwrap();
         let mut reader = BufReader::new(stdout);
 
         'events: loop {
-            let new_volume = volume();
+            let new_volume = volume(&config);
             {
                 let mut df = conc.lock.lock().unwrap();
                 if df.volume != new_volume {
                     df.volume = new_volume;
-                    conc.condition.notify_one();
+                    conc.condition.notify_all();
                 }
             }
             let mut line = String::new();
@@ -305,12 +521,7 @@ fn display_thread(conc: Arc<Concurrency>) {
     let root_window = screen.root();
 
     loop {
-        let new_status;
-        {
-            let mut df = conc.lock.lock().unwrap();
-            df = conc.condition.wait(df).unwrap();
-            new_status = df.to_string();
-        }
+        let snapshot = conc.wait_for_change();
         xcb::xproto::change_property(
             &xconn,
             xcb::xproto::PROP_MODE_REPLACE as u8,
@@ -318,13 +529,40 @@ fn display_thread(conc: Arc<Concurrency>) {
             xcb::xproto::ATOM_WM_NAME,
             xcb::xproto::ATOM_STRING,
             8,
-            new_status.as_bytes(),
+            snapshot.status.as_bytes(),
         );
         xconn.flush();
     }
 }
 
+/// Overrides `config.log` from `--log-path <path>` / `--log-level <level>`,
+/// so logging can be turned on for a single run without editing the config
+/// file.
+fn apply_log_args(config: &mut Config) {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--log-path" => {
+                if let Some(path) = args.next() {
+                    config.log.path = Some(std::path::PathBuf::from(path));
+                }
+            }
+            "--log-level" => {
+                if let Some(level) = args.next().and_then(|v| log::Level::from_str(&v)) {
+                    config.log.level = level;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
 fn main() {
+    let mut config = config::load().unwrap_or_else(|_| Config::default());
+    apply_log_args(&mut config);
+    log::init(config.log.path.as_deref(), config.log.level);
+    let config = Arc::new(config);
+
     let conc = Arc::new(Concurrency {
         lock: Mutex::new(DisplayFields {
             time: String::new(),
@@ -332,33 +570,45 @@ fn main() {
             temp: String::new(),
             net: String::new(),
             volume: String::new(),
+            order: config.order.clone(),
         }),
         condition: Condvar::new(),
     });
 
     {
         let conc2 = Arc::clone(&conc);
-        thread::spawn(move || time_thread(conc2));
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || time_thread(conc2, config2));
+    }
+
+    {
+        let conc2 = Arc::clone(&conc);
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || systemstat_thread(conc2, config2));
     }
 
     {
         let conc2 = Arc::clone(&conc);
-        thread::spawn(move || systemstat_thread(conc2));
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || sensors_thread(conc2, config2));
     }
 
     {
         let conc2 = Arc::clone(&conc);
-        thread::spawn(move || sensors_thread(conc2));
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || network_thread(conc2, config2));
     }
 
     {
         let conc2 = Arc::clone(&conc);
-        thread::spawn(move || network_thread(conc2));
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || volume_thread(conc2, config2));
     }
 
     {
         let conc2 = Arc::clone(&conc);
-        thread::spawn(move || volume_thread(conc2));
+        let config2 = Arc::clone(&config);
+        thread::spawn(move || mqtt::mqtt_thread(conc2, config2));
     }
 
     display_thread(conc);
@@ -0,0 +1,101 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time;
+
+use rumqttc::{Client, MqttOptions, QoS};
+
+use crate::config::{Config, MqttConfig};
+use crate::{Concurrency, Snapshot};
+
+const INITIAL_BACKOFF: time::Duration = time::Duration::from_secs(1);
+const MAX_BACKOFF: time::Duration = time::Duration::from_secs(60);
+
+fn hostname() -> String {
+    hostname::get()
+        .ok()
+        .and_then(|name| name.into_string().ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Connects to the broker and spawns the background thread that drives
+/// rumqttc's event loop. Returns the client plus a flag the event-loop
+/// thread flips to `false` the moment it reports a connection error, so
+/// `mqtt_thread` can notice the broker dropped out and reconnect instead of
+/// treating a dead link as healthy forever.
+fn connect(mqtt: &MqttConfig, host_id: &str) -> (Client, Arc<AtomicBool>) {
+    let mut opts = MqttOptions::new(
+        format!("dwmstatus-{}", host_id),
+        mqtt.host
+            .clone()
+            .expect("mqtt thread started without a host configured"),
+        mqtt.port,
+    );
+    if let (Some(username), Some(password)) = (&mqtt.username, &mqtt.password) {
+        opts.set_credentials(username.clone(), password.clone());
+    }
+    let (client, mut connection) = Client::new(opts, 10);
+    let healthy = Arc::new(AtomicBool::new(true));
+    let healthy2 = Arc::clone(&healthy);
+    thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                Ok(_) => {
+                    log_recovery!("mqtt_connection");
+                    healthy2.store(true, Ordering::SeqCst);
+                }
+                Err(err) => {
+                    log_failure!("mqtt_connection", "mqtt connection error: {}", err);
+                    healthy2.store(false, Ordering::SeqCst);
+                }
+            }
+        }
+    });
+    (client, healthy)
+}
+
+fn publish(client: &mut Client, topic: String, payload: &str) -> bool {
+    client
+        .publish(topic, QoS::AtLeastOnce, true, payload.as_bytes())
+        .is_ok()
+}
+
+fn publish_snapshot(client: &mut Client, host_id: &str, snapshot: &Snapshot) -> bool {
+    let base = format!("dwmstatus/{}", host_id);
+    let mut ok = true;
+    ok &= publish(client, format!("{}/volume", base), &snapshot.volume);
+    ok &= publish(client, format!("{}/net", base), &snapshot.net);
+    ok &= publish(client, format!("{}/systemstat", base), &snapshot.systemstat);
+    ok &= publish(client, format!("{}/temp", base), &snapshot.temp);
+    ok &= publish(client, format!("{}/time", base), &snapshot.time);
+    ok &= publish(client, format!("{}/status", base), &snapshot.status);
+    ok
+}
+
+/// Mirrors every `DisplayFields` change onto an MQTT broker, one retained
+/// topic per segment plus a combined `.../status` topic, so other devices on
+/// the network can subscribe to the bar's contents. Reconnects with
+/// exponential backoff if the broker connection drops. No-op if `mqtt.host`
+/// isn't set in the config file.
+pub fn mqtt_thread(conc: Arc<Concurrency>, config: Arc<Config>) {
+    if !config.mqtt.enabled() {
+        return;
+    }
+    let host_id = hostname();
+
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let (mut client, healthy) = connect(&config.mqtt, &host_id);
+        loop {
+            let snapshot = conc.wait_for_change();
+            if !healthy.load(Ordering::SeqCst) || !publish_snapshot(&mut client, &host_id, &snapshot) {
+                break;
+            }
+            // The link was not flagged unhealthy and every publish was queued
+            // to the event loop, so forget how long the last outage was.
+            backoff = INITIAL_BACKOFF;
+        }
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}